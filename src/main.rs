@@ -1,83 +1,456 @@
-use axum::{http::StatusCode, routing::get, Json, Router};
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
 use once_cell::sync::Lazy;
-use rusb::{Context, Device, DeviceDescriptor, Direction, TransferType, UsbContext};
+use rusb::{
+    Context, Device, DeviceDescriptor, DeviceHandle, Direction, Hotplug, HotplugBuilder,
+    TransferType, UsbContext,
+};
 use serde::Serialize;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::convert::Into;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::string::ToString;
-use std::time::Duration;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
+/// Devices are keyed by their USB topology address (bus number, device
+/// address), which is stable for as long as a device stays plugged into the
+/// same port and is what `lsusb` uses to name a device.
+pub type DeviceKey = (u8, u8);
+
 #[derive(Serialize, Clone, Debug)]
 pub struct DaemonState {
     pub uptime: u64,
     pub status: String,
 
+    pub bus: u8,
+    pub address: u8,
     pub vendor_id: u16,
     pub product_id: u16,
     pub device_name: String,
     pub battery_capacity: u32,
+    pub runtime_to_empty: u32,
+    pub voltage: u32,
     pub output_wattage: u32,
     pub output_va: u32,
+
+    // The OUT endpoint `sniff` discovered, used by the command path to write
+    // reports when the device exposes one. Internal wiring, not part of the API.
+    #[serde(skip)]
+    pub out_endpoint: Option<(u8, TransferType)>,
+}
+
+impl DaemonState {
+    fn new(bus: u8, address: u8, desc: &DeviceDescriptor, name: String) -> Self {
+        DaemonState {
+            uptime: 0,
+            status: "running".into(),
+            bus,
+            address,
+            vendor_id: desc.vendor_id(),
+            product_id: desc.product_id(),
+            device_name: name.trim_end_matches('\0').into(),
+            battery_capacity: 0,
+            runtime_to_empty: 0,
+            voltage: 0,
+            output_wattage: 0,
+            output_va: 0,
+            out_endpoint: None,
+        }
+    }
+}
+
+/// Every tracked UPS, keyed by [`DeviceKey`]. Arriving devices are inserted by
+/// the hotplug callback and removed again when they are unplugged.
+pub static DEVICES: Lazy<RwLock<HashMap<DeviceKey, DaemonState>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// The single libusb context shared by the hotplug monitor, the sniffers, and
+/// the command path so they all observe the same device handles.
+pub static CONTEXT: Lazy<Context> =
+    Lazy::new(|| Context::new().expect("Couldn't create USB context"));
+
+const CAPTURE_RING_CAPACITY: usize = 1024;
+const CAPTURE_LOG_MAX_BYTES: u64 = 1 << 20; // rotate the text log past 1 MiB
+const CAPTURE_LOG_PATH: &str = "pwrusb-capture.log";
+const CAPTURE_PCAP_PATH: &str = "pwrusb-capture.pcap";
+// LINKTYPE_USER0: raw application data, which Wireshark lets you assign a
+// dissector to by hand.
+const PCAP_LINKTYPE_USER0: u32 = 147;
+
+/// One captured report, timestamped and tagged by its leading report byte.
+#[derive(Serialize, Clone, Debug)]
+pub struct CaptureEntry {
+    pub timestamp_ms: u128,
+    pub bus: u8,
+    pub address: u8,
+    pub report_id: u8,
+    pub data: String,
+}
+
+/// A report-id filter, mirroring how a usbmon filter narrows a stream: keep
+/// only the listed ids, drop the listed ids, or pass everything through.
+#[derive(Clone, Debug)]
+pub enum CaptureFilter {
+    All,
+    Only(Vec<u8>),
+    Exclude(Vec<u8>),
+}
+
+impl CaptureFilter {
+    /// Parse expressions such as "only report id 8, 25" or "exclude 11". Any
+    /// integers in the string are the id list; the "exclude" keyword flips the
+    /// sense, and an expression with neither keyword nor ids captures all.
+    fn parse(expr: &str) -> CaptureFilter {
+        let ids: Vec<u8> = expr
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|s| s.parse().ok())
+            .collect();
+        let lower = expr.to_ascii_lowercase();
+        if lower.contains("exclude") {
+            CaptureFilter::Exclude(ids)
+        } else if !ids.is_empty() {
+            CaptureFilter::Only(ids)
+        } else {
+            CaptureFilter::All
+        }
+    }
+
+    fn accepts(&self, report_id: u8) -> bool {
+        match self {
+            CaptureFilter::All => true,
+            CaptureFilter::Only(ids) => ids.contains(&report_id),
+            CaptureFilter::Exclude(ids) => !ids.contains(&report_id),
+        }
+    }
+}
+
+/// A size-bounded append log that rotates to `<path>.1` when it grows too large.
+pub struct RotatingLog {
+    path: String,
+    file: File,
+    written: u64,
+    max: u64,
+}
+
+impl RotatingLog {
+    fn open(path: &str, max: u64) -> std::io::Result<RotatingLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(RotatingLog {
+            path: path.into(),
+            file,
+            written,
+            max,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.written + line.len() as u64 > self.max {
+            let _ = std::fs::rename(&self.path, format!("{}.1", self.path));
+            if let Ok(file) = OpenOptions::new().create(true).append(true).open(&self.path) {
+                self.file = file;
+                self.written = 0;
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.written += line.len() as u64;
+        }
+    }
 }
 
-pub static GLOBAL_STATE: Lazy<RwLock<DaemonState>> = Lazy::new(|| {
-    RwLock::new(DaemonState {
-        uptime: 0,
-        status: "starting".into(),
-        vendor_id: 0,
-        product_id: 0,
-        device_name: "".into(),
-        battery_capacity: 0,
-        output_wattage: 0,
-        output_va: 0,
+/// The capture facility that replaces the old commented-out `println!` probing:
+/// a live ring buffer plus optional text/pcap sinks, all gated by `enabled`.
+pub struct CaptureState {
+    pub enabled: bool,
+    pub filter: CaptureFilter,
+    pub ring: VecDeque<CaptureEntry>,
+    pub log: Option<RotatingLog>,
+    pub pcap: Option<File>,
+}
+
+pub static CAPTURE: Lazy<Mutex<CaptureState>> = Lazy::new(|| {
+    Mutex::new(CaptureState {
+        enabled: false,
+        filter: CaptureFilter::All,
+        ring: VecDeque::with_capacity(CAPTURE_RING_CAPACITY),
+        log: None,
+        pcap: None,
     })
 });
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    println!("Starting pwrusb daemon...");
+fn now_ms() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Write the 24-byte classic pcap global header if the file is still empty.
+fn write_pcap_header(file: &mut File) {
+    if file.metadata().map(|m| m.len()).unwrap_or(0) != 0 {
+        return;
+    }
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&0xa1b2c3d4u32.to_le_bytes()); // magic
+    header.extend_from_slice(&2u16.to_le_bytes()); // version major
+    header.extend_from_slice(&4u16.to_le_bytes()); // version minor
+    header.extend_from_slice(&0i32.to_le_bytes()); // thiszone
+    header.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+    header.extend_from_slice(&65535u32.to_le_bytes()); // snaplen
+    header.extend_from_slice(&PCAP_LINKTYPE_USER0.to_le_bytes());
+    let _ = file.write_all(&header);
+}
+
+/// Append one captured record to the pcap file.
+fn write_pcap_record(file: &mut File, timestamp_ms: u128, data: &[u8]) {
+    let mut record = Vec::with_capacity(16 + data.len());
+    record.extend_from_slice(&((timestamp_ms / 1000) as u32).to_le_bytes());
+    record.extend_from_slice(&(((timestamp_ms % 1000) * 1000) as u32).to_le_bytes());
+    record.extend_from_slice(&(data.len() as u32).to_le_bytes()); // incl_len
+    record.extend_from_slice(&(data.len() as u32).to_le_bytes()); // orig_len
+    record.extend_from_slice(data);
+    let _ = file.write_all(&record);
+}
+
+/// Tee one read buffer into the capture sinks, subject to the active filter.
+/// A no-op when capture is disabled.
+fn record_capture(key: DeviceKey, data: &[u8]) {
+    let mut capture = match CAPTURE.lock() {
+        Ok(capture) => capture,
+        Err(_) => return,
+    };
+    if !capture.enabled || data.is_empty() {
+        return;
+    }
+    let report_id = data[0];
+    if !capture.filter.accepts(report_id) {
+        return;
+    }
+
+    let timestamp_ms = now_ms();
+    let entry = CaptureEntry {
+        timestamp_ms,
+        bus: key.0,
+        address: key.1,
+        report_id,
+        data: hex(data),
+    };
+
+    if let Some(log) = capture.log.as_mut() {
+        log.write_line(&format!(
+            "{} bus{} addr{} id={} {}\n",
+            timestamp_ms, key.0, key.1, report_id, entry.data
+        ));
+    }
+    if let Some(pcap) = capture.pcap.as_mut() {
+        write_pcap_record(pcap, timestamp_ms, data);
+    }
+
+    if capture.ring.len() == CAPTURE_RING_CAPACITY {
+        capture.ring.pop_front();
+    }
+    capture.ring.push_back(entry);
+}
+
+/// Turn capture on and apply `filter`, opening the text and pcap sinks if they
+/// are not already open. Sinks stay open across `stop_capture`.
+fn start_capture(filter: CaptureFilter) {
+    let mut capture = match CAPTURE.lock() {
+        Ok(capture) => capture,
+        Err(_) => return,
+    };
+    capture.enabled = true;
+    capture.filter = filter;
+    if capture.log.is_none() {
+        capture.log = RotatingLog::open(CAPTURE_LOG_PATH, CAPTURE_LOG_MAX_BYTES).ok();
+    }
+    if capture.pcap.is_none() {
+        if let Ok(mut file) = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(CAPTURE_PCAP_PATH)
+        {
+            write_pcap_header(&mut file);
+            capture.pcap = Some(file);
+        }
+    }
+}
+
+fn stop_capture() {
+    if let Ok(mut capture) = CAPTURE.lock() {
+        capture.enabled = false;
+    }
+}
+
+// HID Usage Pages we decode. The UPS reports live on the Power Device page
+// (0x84) and the Battery System page (0x85); everything else is ignored.
+const PAGE_POWER_DEVICE: u16 = 0x84;
+const PAGE_BATTERY_SYSTEM: u16 = 0x85;
+
+// Fully-qualified Usages, encoded as (page << 16) | id so a single map key
+// can distinguish e.g. Power Device Voltage from a same-id usage elsewhere.
+const USAGE_VOLTAGE: u32 = ((PAGE_POWER_DEVICE as u32) << 16) | 0x30;
+const USAGE_APPARENT_POWER: u32 = ((PAGE_POWER_DEVICE as u32) << 16) | 0x33;
+const USAGE_ACTIVE_POWER: u32 = ((PAGE_POWER_DEVICE as u32) << 16) | 0x34;
+const USAGE_REMAINING_CAPACITY: u32 = ((PAGE_BATTERY_SYSTEM as u32) << 16) | 0x66;
+const USAGE_RUNTIME_TO_EMPTY: u32 = ((PAGE_BATTERY_SYSTEM as u32) << 16) | 0x68;
+
+/// Where a single logical field lives inside the reports of one Report ID, as
+/// recovered from the HID Report descriptor. Offsets and widths are in bits and
+/// are relative to the first byte *after* the leading Report ID byte.
+#[derive(Clone, Copy, Debug)]
+struct FieldLoc {
+    bit_offset: usize,
+    bit_width: usize,
+    signed: bool,
+    // The HID Unit and Unit Exponent in force when the field was declared. The
+    // exponent is only meaningful for a dimensioned field (`unit != 0`); a
+    // dimensionless value like a percentage or a count is never scaled.
+    unit: u32,
+    unit_exponent: i8,
+}
+
+/// Decoded layout of a device's reports: for each (Report ID, Usage) pair, the
+/// bit range carrying that value. Report ID 0 is the implicit report used when
+/// the descriptor declares no Report ID items.
+type ReportLayout = HashMap<(u8, u32), FieldLoc>;
+
+/// Hotplug callback that keeps [`DEVICES`] in sync with the bus: every arriving
+/// UPS gets its own `sniff` task, and departing ones are dropped from the map.
+struct UpsMonitor;
+
+impl<T: UsbContext> Hotplug<T> for UpsMonitor {
+    fn device_arrived(&mut self, device: Device<T>) {
+        register_device(&device);
+    }
+
+    fn device_left(&mut self, device: Device<T>) {
+        // This callback fires for every unplug on the bus, so only react to
+        // devices we were actually tracking.
+        let key = (device.bus_number(), device.address());
+        if DEVICES.blocking_write().remove(&key).is_some() {
+            println!("UPS device left: bus {} addr {}", key.0, key.1);
+        }
+    }
+}
+
+/// Identify a freshly-seen device and, if it is a UPS we handle, record it and
+/// start sniffing it. Shared by the hotplug callback and the startup scan.
+fn register_device<T: UsbContext>(device: &Device<T>) {
+    let desc = match device.device_descriptor() {
+        Ok(desc) => desc,
+        Err(_) => return,
+    };
+    let name = get_device_name(device, &desc).unwrap_or_else(|_| "<unknown>".into());
+    if !name.contains("CPS") {
+        return;
+    }
+
+    let key = (device.bus_number(), device.address());
+    println!("Found UPS device: {} (bus {} addr {})", name, key.0, key.1);
     {
-        tokio::spawn(async {
-            loop {
-                {
-                    let mut state = GLOBAL_STATE.write().await;
-                    state.uptime += 1;
-                }
-                tokio::time::sleep(Duration::from_secs(1)).await;
-            }
-        });
+        let mut devices = DEVICES.blocking_write();
+        devices.insert(key, DaemonState::new(key.0, key.1, &desc, name));
     }
 
-    let context = Context::new().expect("Couldn't create context");
-    let devices = context.devices().expect("Failed to list devices");
+    println!("Collecting UPS data...");
+    let device_clone = device.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = sniff(&device_clone, key) {
+            println!("USB sniff failed for bus {} addr {}: {:?}", key.0, key.1, e);
+        }
+        // Whatever ended the sniff loop (unplug, error) leaves the device
+        // untracked so a later replug starts cleanly.
+        DEVICES.blocking_write().remove(&key);
+    });
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    println!("Starting pwrusb daemon...");
 
-    for device in devices.iter() {
-        let desc = device
-            .device_descriptor()
-            .expect("Failed to read device descriptor");
-        let name = get_device_name(&device, &desc).unwrap_or("<unknown>".into());
+    // Capture can be armed from the environment so a noisy device can be logged
+    // from the first report, before any HTTP client connects.
+    if let Ok(expr) = std::env::var("PWRUSB_CAPTURE") {
+        println!("Capture enabled from PWRUSB_CAPTURE=\"{}\"", expr);
+        start_capture(CaptureFilter::parse(&expr));
+    }
 
-        if name.contains("CPS") {
+    tokio::spawn(async {
+        loop {
             {
-                let mut state = GLOBAL_STATE.write().await;
-                state.status = "running".into();
-                state.device_name = name.clone().trim_end_matches('\0').into();
-                state.vendor_id = desc.vendor_id();
-                state.product_id = desc.product_id();
+                let mut devices = DEVICES.write().await;
+                for state in devices.values_mut() {
+                    state.uptime += 1;
+                }
             }
-            println!("Found UPS device: {}", name);
-            // println!("{}: {} {}", name, desc.vendor_id(), desc.product_id());
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
 
-            println!("Collecting UPS data...");
-            let device_clone = device.clone();
-            tokio::task::spawn_blocking(move || {
-                sniff(&device_clone).expect("USB sniff failed");
-            });
+    // Discovery runs on its own OS thread: `register_device` takes
+    // `DEVICES.blocking_write()`, which panics inside the async runtime, and the
+    // `enumerate` replay can invoke it synchronously from `register()`.
+    //
+    // Prefer libusb hotplug: `enumerate` replays the already-connected devices
+    // through the same callback, so the startup scan and ongoing monitoring
+    // share one code path. Fall back to a one-shot scan where it is unsupported.
+    std::thread::spawn(move || {
+        let context = CONTEXT.clone();
+        if rusb::has_hotplug() {
+            let mut reg = Some(
+                HotplugBuilder::new()
+                    .enumerate(true)
+                    .register(&context, Box::new(UpsMonitor))
+                    .expect("Failed to register hotplug callback"),
+            );
+            loop {
+                if let Err(e) = context.handle_events(None) {
+                    println!("Hotplug event loop error: {:?}", e);
+                    if let Some(reg) = reg.take() {
+                        context.unregister_callback(reg);
+                    }
+                    break;
+                }
+            }
+        } else {
+            println!("Hotplug unsupported; performing one-shot device scan.");
+            match context.devices() {
+                Ok(devices) => {
+                    for device in devices.iter() {
+                        register_device(&device);
+                    }
+                }
+                Err(e) => println!("Failed to list devices: {:?}", e),
+            }
         }
-    }
+    });
 
     println!("Starting pwrusb HTTP server...");
-    let app = Router::new().route("/", get(list_info));
+    let app = Router::new()
+        .route("/", get(list_info))
+        .route("/device/:bus/:addr", get(get_device))
+        .route("/device/:bus/:addr/command/:name", axum::routing::post(run_command))
+        .route("/usb", get(list_usb))
+        .route("/capture/start", axum::routing::post(capture_start))
+        .route("/capture/stop", axum::routing::post(capture_stop))
+        .route("/capture/recent", get(capture_recent));
     let listener = tokio::net::TcpListener::bind("0.0.0.0:37473").await?;
     axum::serve(listener, app).await?;
 
@@ -98,7 +471,153 @@ fn get_device_name<T: UsbContext>(
     Ok(format!("{} {}", manufacturer, product))
 }
 
-fn sniff<T: UsbContext>(device: &Device<T>) -> rusb::Result<()> {
+/// Parse a HID Report descriptor into a [`ReportLayout`].
+///
+/// The descriptor is a flat stream of short items; the first byte of each item
+/// encodes `bSize` (bits 0-1, meaning 0/1/2/4 data bytes), `bType` (bits 2-3:
+/// Main/Global/Local) and `bTag` (bits 4-7). Global items form a persistent
+/// state (Usage Page, Report Size/Count/ID, Logical Min/Max, Unit Exponent);
+/// Local items accumulate a Usage list that resets after every Main item. Each
+/// Input/Feature Main item lays out `Report Count` fields of `Report Size` bits,
+/// consuming one Usage apiece and advancing the bit cursor for its Report ID.
+fn parse_report_descriptor(desc: &[u8]) -> ReportLayout {
+    let mut layout: ReportLayout = HashMap::new();
+
+    // Global state, carried across items until overwritten.
+    let mut usage_page: u16 = 0;
+    let mut report_size: u32 = 0;
+    let mut report_count: u32 = 0;
+    let mut report_id: u8 = 0;
+    let mut logical_min: u32 = 0;
+    let mut logical_max: u32 = 0;
+    let mut unit: u32 = 0;
+    let mut unit_exponent: i8 = 0;
+
+    // Local state, reset after each Main item.
+    let mut usages: Vec<u32> = Vec::new();
+
+    // Running bit cursor within each Report ID's report body.
+    let mut bit_cursor: HashMap<u8, usize> = HashMap::new();
+
+    let mut i = 0;
+    while i < desc.len() {
+        let prefix = desc[i];
+        i += 1;
+        let b_size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let b_type = (prefix >> 2) & 0x03;
+        let b_tag = prefix >> 4;
+
+        let mut data: u32 = 0;
+        for j in 0..b_size {
+            if let Some(byte) = desc.get(i + j) {
+                data |= (*byte as u32) << (8 * j);
+            }
+        }
+        i += b_size;
+
+        match b_type {
+            0 => {
+                // Main item. Input (0x08) and Feature (0x0b) carry data fields;
+                // Output would too but UPSes expose their telemetry as Input.
+                if b_tag == 0x08 || b_tag == 0x0b {
+                    // Logical Min > Max under an unsigned reading means the field
+                    // is actually signed.
+                    let signed = logical_min > logical_max;
+                    let cursor = bit_cursor.entry(report_id).or_insert(0);
+                    for k in 0..report_count as usize {
+                        // The last Usage repeats once the list runs out, matching
+                        // how arrays of identical controls are declared.
+                        let usage = usages
+                            .get(k)
+                            .or_else(|| usages.last())
+                            .copied()
+                            .unwrap_or(0);
+                        let full = if usage > 0xffff {
+                            usage
+                        } else {
+                            ((usage_page as u32) << 16) | usage
+                        };
+                        layout.insert(
+                            (report_id, full),
+                            FieldLoc {
+                                bit_offset: *cursor,
+                                bit_width: report_size as usize,
+                                signed,
+                                unit,
+                                unit_exponent,
+                            },
+                        );
+                        *cursor += report_size as usize;
+                    }
+                }
+                usages.clear();
+            }
+            1 => match b_tag {
+                0x0 => usage_page = data as u16,
+                0x1 => logical_min = data,
+                0x2 => logical_max = data,
+                0x5 => {
+                    // Unit Exponent is a 4-bit signed nibble: 0x8..=0xf are -8..=-1.
+                    let n = (data & 0xf) as i8;
+                    unit_exponent = if n > 7 { n - 16 } else { n };
+                }
+                0x6 => unit = data,
+                0x7 => report_size = data,
+                0x8 => report_id = data as u8,
+                0x9 => report_count = data,
+                _ => {}
+            },
+            2 => {
+                // Local item. Only plain Usage (0x0) is needed for telemetry.
+                if b_tag == 0x0 {
+                    usages.push(data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    layout
+}
+
+/// Read `loc.bit_width` bits little-endian starting at `loc.bit_offset` from a
+/// report body, returning the value sign-extended per `loc.signed`.
+fn extract_field(body: &[u8], loc: &FieldLoc) -> i64 {
+    let mut raw: u64 = 0;
+    for b in 0..loc.bit_width {
+        let bit = loc.bit_offset + b;
+        if let Some(byte) = body.get(bit / 8) {
+            raw |= (((*byte >> (bit % 8)) & 1) as u64) << b;
+        }
+    }
+    if loc.signed && loc.bit_width > 0 && raw & (1 << (loc.bit_width - 1)) != 0 {
+        raw as i64 - (1i64 << loc.bit_width)
+    } else {
+        raw as i64
+    }
+}
+
+/// Apply the HID Unit Exponent (a base-10 scale) and clamp to the unsigned
+/// integers [`DaemonState`] stores. The exponent only describes a dimensioned
+/// quantity, so a dimensionless field (no `Unit`) is returned verbatim — its
+/// carried-over global exponent must not be applied.
+fn scale(value: i64, loc: &FieldLoc) -> u32 {
+    let scaled = if loc.unit != 0 {
+        value as f64 * 10f64.powi(loc.unit_exponent as i32)
+    } else {
+        value as f64
+    };
+    if scaled <= 0.0 {
+        0
+    } else {
+        scaled as u32
+    }
+}
+
+fn sniff<T: UsbContext>(device: &Device<T>, key: DeviceKey) -> rusb::Result<()> {
     let handle = device.open()?;
 
     if handle.kernel_driver_active(0)? {
@@ -107,25 +626,52 @@ fn sniff<T: UsbContext>(device: &Device<T>) -> rusb::Result<()> {
 
     handle.claim_interface(0)?;
 
+    // Pull the HID Report descriptor (GET_DESCRIPTOR, type 0x22) so we can map
+    // report bytes onto real Usages instead of guessing at positional tags.
+    let mut raw_desc = [0u8; 4096];
+    let layout = match handle.read_control(
+        0x81,
+        0x06, // GET_DESCRIPTOR
+        0x2200,
+        0,
+        &mut raw_desc,
+        Duration::from_secs(1),
+    ) {
+        Ok(n) => parse_report_descriptor(&raw_desc[..n]),
+        Err(e) => {
+            println!("Failed to read report descriptor: {:?}", e);
+            ReportLayout::new()
+        }
+    };
+    // A descriptor with no Report ID item addresses a single implicit report 0.
+    let uses_report_ids = layout.keys().any(|(id, _)| *id != 0);
+
     let config = device.active_config_descriptor()?;
     let mut in_endpoint: Option<(u8, TransferType)> = None;
+    let mut out_endpoint: Option<(u8, TransferType)> = None;
 
     for interface in config.interfaces() {
         for iface_desc in interface.descriptors() {
             for endpoint in iface_desc.endpoint_descriptors() {
-                // println!(
-                //     "Found endpoint 0x{:02x} dir={:?} type={:?}",
-                //     endpoint.address(),
-                //     endpoint.direction(),
-                //     endpoint.transfer_type()
-                // );
-                if endpoint.direction() == Direction::In {
-                    in_endpoint = Some((endpoint.address(), endpoint.transfer_type()));
+                match endpoint.direction() {
+                    Direction::In => {
+                        in_endpoint = Some((endpoint.address(), endpoint.transfer_type()));
+                    }
+                    Direction::Out if out_endpoint.is_none() => {
+                        out_endpoint = Some((endpoint.address(), endpoint.transfer_type()));
+                    }
+                    Direction::Out => {}
                 }
             }
         }
     }
 
+    // Publish the OUT endpoint (if any) so the command path can write to it
+    // rather than falling back to a SET_REPORT control transfer.
+    if let Some(state) = DEVICES.blocking_write().get_mut(&key) {
+        state.out_endpoint = out_endpoint;
+    }
+
     let (ep, ttype) = in_endpoint.expect("No IN endpoint found");
 
     let mut buf = [0u8; 64];
@@ -140,38 +686,36 @@ fn sniff<T: UsbContext>(device: &Device<T>) -> rusb::Result<()> {
 
         match result {
             Ok(n) if n > 0 => {
-                let mut a: Vec<u32> = Vec::new();
-                for b in &buf[..n] {
-                    let h: u32 = *b as u32;
-                    a.push(h);
-                }
+                // Tee the raw read into the capture facility before decoding.
+                record_capture(key, &buf[..n]);
 
-                // Mainly for testing to see if any other values are ever received
-                if a[0] != 8 && a[0] != 11 && a[0] != 25 && a[0] != 29 {
-                    println!("{:?}", a);
-                }
+                // Split the leading Report ID (when used) from the body, then
+                // decode every field declared for that Report ID.
+                let (report_id, body) = if uses_report_ids {
+                    (buf[0], &buf[1..n])
+                } else {
+                    (0u8, &buf[..n])
+                };
 
-                if a[0] == 8 {
-                    let mut state = GLOBAL_STATE.blocking_write();
-                    state.battery_capacity = a[1];
-                    // println!("Battery Capacity: \t{}%    {:?}", a[1], a)
-                }
-                if a[0] == 25 {
-                    let mut state = GLOBAL_STATE.blocking_write();
-                    state.output_wattage = a[1] + (a[2] * 256);
-                    // println!("Output Wattage: \t{}W", a[1] + (a[2] * 256))
+                let mut devices = DEVICES.blocking_write();
+                let state = match devices.get_mut(&key) {
+                    Some(state) => state,
+                    None => break, // device was unplugged out from under us
+                };
+                for ((id, usage), loc) in layout.iter() {
+                    if *id != report_id {
+                        continue;
+                    }
+                    let value = scale(extract_field(body, loc), loc);
+                    match *usage {
+                        USAGE_REMAINING_CAPACITY => state.battery_capacity = value,
+                        USAGE_RUNTIME_TO_EMPTY => state.runtime_to_empty = value,
+                        USAGE_VOLTAGE => state.voltage = value,
+                        USAGE_ACTIVE_POWER => state.output_wattage = value,
+                        USAGE_APPARENT_POWER => state.output_va = value,
+                        _ => {}
+                    }
                 }
-                if a[0] == 29 {
-                    let mut state = GLOBAL_STATE.blocking_write();
-                    state.output_va = a[1] + (a[2] * 256);
-                    // println!("Output VA: \t\t{}", a[1] + (a[2] * 256))
-                }
-
-                // This is a small documentation of values received and their descriptions
-                // 8; first number battery cap
-                // 11 unknown
-                // 25 is the output W, ex: [25, 100, 0], the last number, 0, is an indicator of how many times the output in this instance should be multiplied (maximum int value of 256 or 255 (unsure, need to test more))
-                // 29 is the output VA
             }
             Ok(_) => {}
             Err(rusb::Error::Timeout) => {}
@@ -185,7 +729,395 @@ fn sniff<T: UsbContext>(device: &Device<T>) -> rusb::Result<()> {
     Ok(())
 }
 
-async fn list_info() -> (StatusCode, Json<DaemonState>) {
-    let state = GLOBAL_STATE.read().await;
-    (StatusCode::OK, Json(state.clone()))
+/// A full device descriptor tree, mirroring what `lsusb -v` prints. String
+/// fields are `null` when the descriptor omits the index or the device can't be
+/// opened to read it.
+#[derive(Serialize, Clone, Debug)]
+pub struct UsbDevice {
+    pub bus: u8,
+    pub address: u8,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub usb_version: String,
+    pub class: u8,
+    pub sub_class: u8,
+    pub protocol: u8,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub configurations: Vec<UsbConfiguration>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UsbConfiguration {
+    pub number: u8,
+    pub attributes: u8,
+    pub max_power_ma: u16,
+    pub interfaces: Vec<UsbInterface>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UsbInterface {
+    pub number: u8,
+    pub alt_setting: u8,
+    pub class: u8,
+    pub sub_class: u8,
+    pub protocol: u8,
+    pub description: Option<String>,
+    pub endpoints: Vec<UsbEndpoint>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UsbEndpoint {
+    pub address: u8,
+    pub direction: String,
+    pub transfer_type: String,
+    pub max_packet_size: u16,
+    pub interval: u8,
+}
+
+/// Walk a device's descriptors into a [`UsbDevice`] tree. Opening the device is
+/// best-effort: string descriptors fall back to `null` when it is unavailable.
+fn describe_device<T: UsbContext>(device: &Device<T>) -> Option<UsbDevice> {
+    let desc = device.device_descriptor().ok()?;
+    let handle = device.open().ok();
+    let version = desc.usb_version();
+
+    let mut configurations = Vec::new();
+    for i in 0..desc.num_configurations() {
+        let config = match device.config_descriptor(i) {
+            Ok(config) => config,
+            Err(_) => continue,
+        };
+        let mut interfaces = Vec::new();
+        for interface in config.interfaces() {
+            for iface in interface.descriptors() {
+                let description = iface
+                    .description_string_index()
+                    .and_then(|idx| handle.as_ref().and_then(|h| h.read_string_descriptor_ascii(idx).ok()));
+                let endpoints = iface
+                    .endpoint_descriptors()
+                    .map(|ep| UsbEndpoint {
+                        address: ep.address(),
+                        direction: direction_name(ep.direction()),
+                        transfer_type: transfer_type_name(ep.transfer_type()),
+                        max_packet_size: ep.max_packet_size(),
+                        interval: ep.interval(),
+                    })
+                    .collect();
+                interfaces.push(UsbInterface {
+                    number: iface.interface_number(),
+                    alt_setting: iface.setting_number(),
+                    class: iface.class_code(),
+                    sub_class: iface.sub_class_code(),
+                    protocol: iface.protocol_code(),
+                    description,
+                    endpoints,
+                });
+            }
+        }
+        configurations.push(UsbConfiguration {
+            number: config.number(),
+            attributes: config.attributes(),
+            max_power_ma: config.max_power(),
+            interfaces,
+        });
+    }
+
+    Some(UsbDevice {
+        bus: device.bus_number(),
+        address: device.address(),
+        vendor_id: desc.vendor_id(),
+        product_id: desc.product_id(),
+        usb_version: format!("{}.{}{}", version.major(), version.minor(), version.sub_minor()),
+        class: desc.class_code(),
+        sub_class: desc.sub_class_code(),
+        protocol: desc.protocol_code(),
+        manufacturer: handle.as_ref().and_then(|h| h.read_manufacturer_string_ascii(&desc).ok()),
+        product: handle.as_ref().and_then(|h| h.read_product_string_ascii(&desc).ok()),
+        serial_number: handle.as_ref().and_then(|h| h.read_serial_number_string_ascii(&desc).ok()),
+        configurations,
+    })
+}
+
+fn direction_name(direction: Direction) -> String {
+    match direction {
+        Direction::In => "in".into(),
+        Direction::Out => "out".into(),
+    }
+}
+
+fn transfer_type_name(transfer_type: TransferType) -> String {
+    match transfer_type {
+        TransferType::Control => "control".into(),
+        TransferType::Isochronous => "isochronous".into(),
+        TransferType::Bulk => "bulk".into(),
+        TransferType::Interrupt => "interrupt".into(),
+    }
+}
+
+/// Translate a command name into the Feature report (Report ID + payload) that
+/// actuates it. These are the common HID Power Device control reports
+/// (AudibleAlarmControl, Test, and a per-outlet switch); unknown names map to
+/// `None` so the handler can answer 404.
+fn command_payload(name: &str) -> Option<(u8, Vec<u8>)> {
+    match name {
+        // AudibleAlarmControl = Disabled.
+        "mute_beeper" => Some((0x0c, vec![0x01])),
+        // Test = quick self-test.
+        "start_self_test" => Some((0x14, vec![0x01])),
+        // SwitchOnOff toggle for the controllable outlet.
+        "toggle_outlet" => Some((0x12, vec![0x01])),
+        _ => None,
+    }
+}
+
+/// Send a command report to the device, preferring a writable OUT endpoint and
+/// otherwise issuing a SET_REPORT (Feature) control transfer on endpoint 0.
+fn send_command<T: UsbContext>(
+    handle: &DeviceHandle<T>,
+    report_id: u8,
+    payload: &[u8],
+    out_endpoint: Option<(u8, TransferType)>,
+) -> rusb::Result<usize> {
+    let timeout = Duration::from_secs(1);
+    match out_endpoint {
+        // Endpoint writes carry the Report ID as the first byte of the buffer.
+        Some((ep, TransferType::Bulk)) => {
+            let mut buf = Vec::with_capacity(1 + payload.len());
+            buf.push(report_id);
+            buf.extend_from_slice(payload);
+            handle.write_bulk(ep, &buf, timeout)
+        }
+        Some((ep, TransferType::Interrupt)) => {
+            let mut buf = Vec::with_capacity(1 + payload.len());
+            buf.push(report_id);
+            buf.extend_from_slice(payload);
+            handle.write_interrupt(ep, &buf, timeout)
+        }
+        // SET_REPORT: bmRequestType 0x21, bRequest 0x09, wValue = (type<<8)|id,
+        // type 0x03 = Feature. The Report ID lives in wValue, not the payload.
+        _ => {
+            let w_value = 0x0300 | report_id as u16;
+            handle.write_control(0x21, 0x09, w_value, 0, payload, timeout)
+        }
+    }
+}
+
+async fn run_command(
+    Path((bus, addr, name)): Path<(u8, u8, String)>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let (report_id, payload) = match command_payload(&name) {
+        Some(cmd) => cmd,
+        None => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({ "error": format!("unknown command '{}'", name) })),
+            );
+        }
+    };
+
+    let out_endpoint = DEVICES.read().await.get(&(bus, addr)).and_then(|s| s.out_endpoint);
+
+    let result = tokio::task::spawn_blocking(move || {
+        let devices = CONTEXT.devices()?;
+        for device in devices.iter() {
+            if device.bus_number() == bus && device.address() == addr {
+                let handle = device.open()?;
+                // The sniffer already owns the interface; a best-effort claim is
+                // enough for control/endpoint writes and is fine if it is busy.
+                let _ = handle.claim_interface(0);
+                return send_command(&handle, report_id, &payload, out_endpoint).map(|_| ());
+            }
+        }
+        Err(rusb::Error::NoDevice)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => (StatusCode::OK, Json(json!({ "status": "ok", "command": name }))),
+        Ok(Err(e)) => (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({ "error": format!("{:?}", e) })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("command task failed: {}", e) })),
+        ),
+    }
+}
+
+async fn list_info() -> (StatusCode, Json<Vec<DaemonState>>) {
+    let devices = DEVICES.read().await;
+    let mut list: Vec<DaemonState> = devices.values().cloned().collect();
+    list.sort_by_key(|state| (state.bus, state.address));
+    (StatusCode::OK, Json(list))
+}
+
+async fn get_device(Path((bus, addr)): Path<(u8, u8)>) -> (StatusCode, Json<Option<DaemonState>>) {
+    let devices = DEVICES.read().await;
+    match devices.get(&(bus, addr)) {
+        Some(state) => (StatusCode::OK, Json(Some(state.clone()))),
+        None => (StatusCode::NOT_FOUND, Json(None)),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct CaptureQuery {
+    filter: Option<String>,
+}
+
+async fn capture_start(
+    axum::extract::Query(query): axum::extract::Query<CaptureQuery>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    let filter = query
+        .filter
+        .as_deref()
+        .map(CaptureFilter::parse)
+        .unwrap_or(CaptureFilter::All);
+    let description = format!("{:?}", filter);
+    start_capture(filter);
+    (StatusCode::OK, Json(json!({ "status": "capturing", "filter": description })))
+}
+
+async fn capture_stop() -> (StatusCode, Json<serde_json::Value>) {
+    stop_capture();
+    (StatusCode::OK, Json(json!({ "status": "stopped" })))
+}
+
+async fn capture_recent() -> (StatusCode, Json<Vec<CaptureEntry>>) {
+    let entries = CAPTURE
+        .lock()
+        .map(|capture| capture.ring.iter().cloned().collect())
+        .unwrap_or_default();
+    (StatusCode::OK, Json(entries))
+}
+
+async fn list_usb() -> (StatusCode, Json<Vec<UsbDevice>>) {
+    let tree = tokio::task::spawn_blocking(|| {
+        let mut out = Vec::new();
+        if let Ok(devices) = CONTEXT.devices() {
+            for device in devices.iter() {
+                if let Some(described) = describe_device(&device) {
+                    out.push(described);
+                }
+            }
+        }
+        out
+    })
+    .await
+    .unwrap_or_default();
+    (StatusCode::OK, Json(tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(layout: &ReportLayout, report_id: u8, usage: u32) -> FieldLoc {
+        *layout
+            .get(&(report_id, usage))
+            .expect("usage missing from layout")
+    }
+
+    #[test]
+    fn parse_descriptor_maps_usages_to_bit_ranges() {
+        // Report 1: RemainingCapacity (8-bit percent, no Unit) followed by
+        // Voltage (16-bit, Unit set with exponent -2).
+        let desc = [
+            0x05, 0x85, // Usage Page (Battery System)
+            0x85, 0x01, // Report ID (1)
+            0x75, 0x08, // Report Size (8)
+            0x95, 0x01, // Report Count (1)
+            0x09, 0x66, // Usage (RemainingCapacity)
+            0x15, 0x00, // Logical Minimum (0)
+            0x25, 0x64, // Logical Maximum (100)
+            0x81, 0x02, // Input
+            0x05, 0x84, // Usage Page (Power Device)
+            0x55, 0x0e, // Unit Exponent (-2)
+            0x65, 0x11, // Unit (non-zero)
+            0x75, 0x10, // Report Size (16)
+            0x95, 0x01, // Report Count (1)
+            0x09, 0x30, // Usage (Voltage)
+            0x15, 0x00, // Logical Minimum (0)
+            0x26, 0xff, 0x7f, // Logical Maximum (32767)
+            0x81, 0x02, // Input
+        ];
+        let layout = parse_report_descriptor(&desc);
+
+        let capacity = field(&layout, 1, USAGE_REMAINING_CAPACITY);
+        assert_eq!(capacity.bit_offset, 0);
+        assert_eq!(capacity.bit_width, 8);
+        assert_eq!(capacity.unit, 0, "percentage must stay dimensionless");
+
+        let voltage = field(&layout, 1, USAGE_VOLTAGE);
+        assert_eq!(voltage.bit_offset, 8);
+        assert_eq!(voltage.bit_width, 16);
+        assert_ne!(voltage.unit, 0);
+        assert_eq!(voltage.unit_exponent, -2);
+    }
+
+    #[test]
+    fn extract_field_reads_little_endian_and_sign() {
+        let unsigned = FieldLoc {
+            bit_offset: 8,
+            bit_width: 16,
+            signed: false,
+            unit: 0,
+            unit_exponent: 0,
+        };
+        // 0x1234 little-endian at byte offset 1.
+        assert_eq!(extract_field(&[0x00, 0x34, 0x12], &unsigned), 0x1234);
+
+        let signed = FieldLoc {
+            bit_offset: 0,
+            bit_width: 8,
+            signed: true,
+            unit: 0,
+            unit_exponent: 0,
+        };
+        assert_eq!(extract_field(&[0xff], &signed), -1);
+    }
+
+    #[test]
+    fn scale_only_applies_exponent_to_dimensioned_fields() {
+        let percent = FieldLoc {
+            bit_offset: 0,
+            bit_width: 8,
+            signed: false,
+            unit: 0,
+            unit_exponent: -2,
+        };
+        // A carried-over exponent must not touch a dimensionless value.
+        assert_eq!(scale(50, &percent), 50);
+
+        let volts = FieldLoc {
+            bit_offset: 0,
+            bit_width: 16,
+            signed: false,
+            unit: 0x11,
+            unit_exponent: -2,
+        };
+        assert_eq!(scale(2300, &volts), 23);
+    }
+
+    #[test]
+    fn capture_filter_parses_only_and_exclude() {
+        match CaptureFilter::parse("only report id 8, 25") {
+            CaptureFilter::Only(ids) => assert_eq!(ids, vec![8, 25]),
+            other => panic!("expected Only, got {:?}", other),
+        }
+        match CaptureFilter::parse("exclude 11") {
+            CaptureFilter::Exclude(ids) => assert_eq!(ids, vec![11]),
+            other => panic!("expected Exclude, got {:?}", other),
+        }
+        assert!(matches!(CaptureFilter::parse(""), CaptureFilter::All));
+
+        let only = CaptureFilter::Only(vec![8, 25]);
+        assert!(only.accepts(8));
+        assert!(!only.accepts(11));
+        let exclude = CaptureFilter::Exclude(vec![11]);
+        assert!(!exclude.accepts(11));
+        assert!(exclude.accepts(8));
+    }
 }